@@ -0,0 +1,505 @@
+//! DAG-backed replacement for `git bisect`.
+//!
+//! Unlike `git bisect`, which walks the commit graph on disk one `git
+//! checkout` at a time, this command computes the candidate search space
+//! directly from the in-memory `Dag`, so it naturally respects obsolete
+//! commits (which are never offered up as the next commit to test) and
+//! skipped commits (which are removed from consideration rather than merely
+//! stepped around).
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
+use std::time::SystemTime;
+
+use eden_dag::DagAlgorithm;
+use rusqlite::{Connection, OptionalExtension};
+use tracing::instrument;
+
+use crate::commands::smartlog::{make_smartlog_graph, render_graph};
+use crate::core::eventlog::{EventLogDb, EventReplayer};
+use crate::core::formatting::printable_styled_string;
+use crate::core::metadata::{
+    BranchesProvider, CandidateHighlightProvider, CommitMessageProvider, CommitOidProvider,
+    DescribeProvider, DifferentialRevisionProvider, ObsolescenceExplanationProvider,
+    RelativeTimeProvider,
+};
+use crate::git::{sort_commit_set, CommitSet, Dag, GitRunInfo, NonZeroOid, Repo};
+use crate::tui::Effects;
+
+/// The user's classification of a commit while searching for the commit that
+/// introduced a bug.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BisectStatus {
+    /// The commit does not exhibit the bug being searched for.
+    Good,
+
+    /// The commit exhibits the bug being searched for.
+    Bad,
+
+    /// The commit couldn't be tested one way or the other, so it is excluded
+    /// from the candidate set. Its neighbors are not excluded.
+    Skip,
+}
+
+/// An action to apply to the current bisect search.
+#[derive(Clone, Debug)]
+pub enum BisectAction {
+    /// Begin a new search with the known-bad commit and one or more
+    /// known-good commits.
+    Start {
+        /// The commit which is known to exhibit the bug.
+        bad: NonZeroOid,
+
+        /// Commits which are known not to exhibit the bug.
+        good: Vec<NonZeroOid>,
+    },
+
+    /// Classify the current `HEAD` commit and advance the search.
+    Mark {
+        /// How to classify the current commit.
+        status: BisectStatus,
+    },
+
+    /// Forget the current search and return to the commit it was started
+    /// from.
+    Reset,
+}
+
+/// Persists the good/bad/skip classifications made during a bisect search, so
+/// that the search can be resumed across invocations of the command.
+struct BisectStateDb<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> BisectStateDb<'a> {
+    #[instrument]
+    fn new(conn: &'a Connection) -> eyre::Result<Self> {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS bisect_state (
+                oid TEXT NOT NULL PRIMARY KEY,
+                status TEXT NOT NULL
+            )",
+            rusqlite::params![],
+        )?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS bisect_meta (
+                key TEXT NOT NULL PRIMARY KEY,
+                value TEXT NOT NULL
+            )",
+            rusqlite::params![],
+        )?;
+        Ok(Self { conn })
+    }
+
+    /// Record the commit that was checked out when the search began, so that
+    /// `Reset` can restore it later.
+    fn set_start_oid(&self, oid: NonZeroOid) -> eyre::Result<()> {
+        self.conn.execute(
+            "INSERT INTO bisect_meta (key, value) VALUES ('start_oid', ?)
+             ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+            rusqlite::params![oid.to_string()],
+        )?;
+        Ok(())
+    }
+
+    /// Retrieve the commit recorded by [`Self::set_start_oid`], if any.
+    fn get_start_oid(&self) -> eyre::Result<Option<NonZeroOid>> {
+        let value: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT value FROM bisect_meta WHERE key = 'start_oid'",
+                rusqlite::params![],
+                |row| row.get(0),
+            )
+            .optional()?;
+        value.map(|oid| oid.parse()).transpose().map_err(Into::into)
+    }
+
+    fn mark(&self, oid: NonZeroOid, status: BisectStatus) -> eyre::Result<()> {
+        let status = match status {
+            BisectStatus::Good => "good",
+            BisectStatus::Bad => "bad",
+            BisectStatus::Skip => "skip",
+        };
+        self.conn.execute(
+            "INSERT INTO bisect_state (oid, status) VALUES (?, ?)
+             ON CONFLICT (oid) DO UPDATE SET status = excluded.status",
+            rusqlite::params![oid.to_string(), status],
+        )?;
+        Ok(())
+    }
+
+    fn get_all(&self) -> eyre::Result<Vec<(NonZeroOid, BisectStatus)>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT oid, status FROM bisect_state")?;
+        let rows = stmt
+            .query_map(rusqlite::params![], |row| {
+                let oid: String = row.get("oid")?;
+                let status: String = row.get("status")?;
+                Ok((oid, status))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut result = Vec::new();
+        for (oid, status) in rows {
+            let oid: NonZeroOid = oid.parse()?;
+            let status = match status.as_str() {
+                "good" => BisectStatus::Good,
+                "bad" => BisectStatus::Bad,
+                "skip" => BisectStatus::Skip,
+                other => eyre::bail!("Unknown bisect status in database: {}", other),
+            };
+            result.push((oid, status));
+        }
+        Ok(result)
+    }
+
+    fn clear(&self) -> eyre::Result<()> {
+        self.conn.execute("DELETE FROM bisect_state", rusqlite::params![])?;
+        self.conn.execute("DELETE FROM bisect_meta", rusqlite::params![])?;
+        Ok(())
+    }
+}
+
+/// Compute the set of commits which are still under suspicion: descendants of
+/// a known-good commit, ancestors of the known-bad commit, minus obsolete and
+/// skipped commits.
+fn candidate_commits(
+    repo: &Repo,
+    dag: &Dag,
+    bad_oid: NonZeroOid,
+    good_oids: &HashSet<NonZeroOid>,
+    skipped_oids: &HashSet<NonZeroOid>,
+) -> eyre::Result<CommitSet> {
+    let bad_set = CommitSet::from(bad_oid);
+    let mut good_to_bad_ranges = Vec::new();
+    for &good_oid in good_oids {
+        let good_set = CommitSet::from(good_oid);
+        let range = dag.query().range(good_set, bad_set.clone())?;
+        let range: HashSet<NonZeroOid> = sort_commit_set(repo, dag, &range)?
+            .iter()
+            .map(|commit| commit.get_oid())
+            .collect();
+        good_to_bad_ranges.push(range);
+    }
+
+    let obsolete_oids: HashSet<NonZeroOid> = sort_commit_set(repo, dag, &dag.obsolete_commits)?
+        .iter()
+        .map(|commit| commit.get_oid())
+        .collect();
+
+    let candidates = narrow_candidates(&good_to_bad_ranges, &obsolete_oids, skipped_oids);
+
+    let mut candidate_set = CommitSet::empty();
+    for oid in candidates {
+        candidate_set = candidate_set.union(&CommitSet::from(oid));
+    }
+    Ok(candidate_set)
+}
+
+/// Pure set algebra underlying [`candidate_commits`]: union the per-good
+/// ranges (each already restricted to ancestors of the bad commit), then
+/// subtract the obsolete and skipped commits. Extracted so that the
+/// candidate-narrowing logic can be unit-tested against hand-built sets
+/// representing small synthetic DAGs, without needing a real `Dag`.
+fn narrow_candidates(
+    good_to_bad_ranges: &[HashSet<NonZeroOid>],
+    obsolete_oids: &HashSet<NonZeroOid>,
+    skipped_oids: &HashSet<NonZeroOid>,
+) -> HashSet<NonZeroOid> {
+    let mut candidates = HashSet::new();
+    for range in good_to_bad_ranges {
+        candidates.extend(range.iter().copied());
+    }
+    candidates.retain(|oid| !obsolete_oids.contains(oid) && !skipped_oids.contains(oid));
+    candidates
+}
+
+/// Among the candidate commits, pick the one whose count of ancestors within
+/// the candidate set is closest to half of the total, i.e. the commit which
+/// most evenly bisects the remaining suspects.
+fn choose_next_commit(
+    repo: &Repo,
+    dag: &Dag,
+    candidates: &CommitSet,
+) -> eyre::Result<Option<NonZeroOid>> {
+    let candidate_commits = sort_commit_set(repo, dag, candidates)?;
+    let candidate_oids: Vec<NonZeroOid> = candidate_commits.iter().map(|c| c.get_oid()).collect();
+
+    let mut ancestor_counts = HashMap::new();
+    for &candidate_oid in &candidate_oids {
+        let num_ancestors = dag
+            .query()
+            .ancestors(CommitSet::from(candidate_oid))?
+            .intersection(candidates)
+            .count()? as usize;
+        ancestor_counts.insert(candidate_oid, num_ancestors);
+    }
+
+    Ok(pick_most_balanced(&candidate_oids, &ancestor_counts))
+}
+
+/// Pure balance-heuristic underlying [`choose_next_commit`]: given the
+/// candidate commits and, for each, how many of the candidates are its
+/// ancestors, pick the one closest to bisecting the set in half. Kept
+/// separate from [`choose_next_commit`] so that it takes plain ancestor
+/// counts rather than a `Dag`, which keeps its tests fast and lets them use
+/// hand-built maps instead of constructed commit graphs.
+fn pick_most_balanced(
+    candidate_oids: &[NonZeroOid],
+    ancestor_counts: &HashMap<NonZeroOid, usize>,
+) -> Option<NonZeroOid> {
+    if candidate_oids.is_empty() {
+        return None;
+    }
+    let half = candidate_oids.len() / 2;
+
+    let mut best_oid = None;
+    let mut best_imbalance = usize::MAX;
+    for &candidate_oid in candidate_oids {
+        let num_ancestors = *ancestor_counts.get(&candidate_oid).unwrap_or(&0);
+        let imbalance = num_ancestors.abs_diff(half);
+        if imbalance < best_imbalance {
+            best_imbalance = imbalance;
+            best_oid = Some(candidate_oid);
+        }
+    }
+    best_oid
+}
+
+/// Run one step of a DAG-backed bisect search.
+#[instrument]
+pub fn bisect(effects: &Effects, git_run_info: &GitRunInfo, action: BisectAction) -> eyre::Result<isize> {
+    let repo = Repo::from_current_dir()?;
+    let references_snapshot = repo.get_references_snapshot()?;
+    let conn = repo.get_db_conn()?;
+    let event_log_db = EventLogDb::new(&conn)?;
+    let event_replayer = EventReplayer::from_event_log_db(effects, &repo, &event_log_db)?;
+    let event_cursor = event_replayer.make_default_cursor();
+    let dag = Dag::open_and_sync(
+        effects,
+        &repo,
+        &event_replayer,
+        event_cursor,
+        &references_snapshot,
+    )?;
+    let bisect_db = BisectStateDb::new(&conn)?;
+
+    if let BisectAction::Reset = action {
+        let start_oid = bisect_db.get_start_oid()?;
+        bisect_db.clear()?;
+        if let Some(start_oid) = start_oid {
+            let result =
+                git_run_info.run(effects, None, &["checkout", &start_oid.to_string()])?;
+            if result != 0 {
+                return Ok(result);
+            }
+        }
+        writeln!(effects.get_output_stream(), "Bisect search reset.")?;
+        return Ok(0);
+    }
+
+    if let BisectAction::Start { bad, good } = &action {
+        // Starting a new search should not be polluted by a previous
+        // search's good/bad/skip classifications.
+        bisect_db.clear()?;
+        if let Some(head_oid) = references_snapshot.head_oid {
+            bisect_db.set_start_oid(head_oid)?;
+        }
+        bisect_db.mark(*bad, BisectStatus::Bad)?;
+        for &good_oid in good {
+            bisect_db.mark(good_oid, BisectStatus::Good)?;
+        }
+    }
+
+    if let BisectAction::Mark { status } = action {
+        let head_oid = match references_snapshot.head_oid {
+            Some(head_oid) => head_oid,
+            None => eyre::bail!("No HEAD present; cannot classify the current commit"),
+        };
+        bisect_db.mark(head_oid, status)?;
+    }
+
+    let all_statuses = bisect_db.get_all()?;
+    let bad_oid = all_statuses
+        .iter()
+        .find(|(_, status)| *status == BisectStatus::Bad)
+        .map(|(oid, _)| *oid);
+    let bad_oid = match bad_oid {
+        Some(bad_oid) => bad_oid,
+        None => eyre::bail!("No bad commit has been specified; run `git bisect start` first"),
+    };
+    let good_oids: HashSet<NonZeroOid> = all_statuses
+        .iter()
+        .filter(|(_, status)| *status == BisectStatus::Good)
+        .map(|(oid, _)| *oid)
+        .collect();
+    let skipped_oids: HashSet<NonZeroOid> = all_statuses
+        .iter()
+        .filter(|(_, status)| *status == BisectStatus::Skip)
+        .map(|(oid, _)| *oid)
+        .collect();
+
+    if good_oids.is_empty() {
+        writeln!(
+            effects.get_output_stream(),
+            "No good commit has been specified yet; check out a known-good commit and run `git bisect good` to establish the known-good boundary."
+        )?;
+        return Ok(0);
+    }
+
+    let candidates = candidate_commits(&repo, &dag, bad_oid, &good_oids, &skipped_oids)?;
+    let num_candidates = candidates.count()?;
+    if num_candidates == 0 {
+        writeln!(
+            effects.get_output_stream(),
+            "No commits remain to test; the search may have been narrowed too far by skipped commits."
+        )?;
+        return Ok(0);
+    }
+
+    let next_oid = choose_next_commit(&repo, &dag, &candidates)?;
+    let next_oid = match next_oid {
+        Some(next_oid) => next_oid,
+        None => {
+            writeln!(
+                effects.get_output_stream(),
+                "No commits remain to test in the suspect range."
+            )?;
+            return Ok(0);
+        }
+    };
+
+    writeln!(
+        effects.get_output_stream(),
+        "Bisecting: {} candidate commit(s) remain.",
+        num_candidates
+    )?;
+
+    let result = git_run_info.run(effects, None, &["checkout", &next_oid.to_string()])?;
+    if result != 0 {
+        return Ok(result);
+    }
+
+    // Re-render the smartlog with the remaining candidates highlighted, so
+    // the user can see at a glance how much of the search space is left.
+    let candidate_oids: HashSet<NonZeroOid> = sort_commit_set(&repo, &dag, &candidates)?
+        .iter()
+        .map(|commit| commit.get_oid())
+        .collect();
+    let graph = make_smartlog_graph(effects, &repo, &dag, &event_replayer, event_cursor, true)?;
+    let lines = render_graph(
+        effects,
+        &repo,
+        &dag,
+        &graph,
+        references_snapshot.head_oid,
+        &mut [
+            &mut CommitOidProvider::new(true)?,
+            &mut RelativeTimeProvider::new(&repo, SystemTime::now())?,
+            &mut ObsolescenceExplanationProvider::new(&event_replayer, event_cursor)?,
+            &mut BranchesProvider::new(&repo, &references_snapshot)?,
+            &mut DescribeProvider::new(&repo, &references_snapshot)?,
+            &mut DifferentialRevisionProvider::new(&repo)?,
+            &mut CandidateHighlightProvider::new(candidate_oids),
+            &mut CommitMessageProvider::new()?,
+        ],
+    )?;
+    for line in lines {
+        writeln!(
+            effects.get_output_stream(),
+            "{}",
+            printable_styled_string(effects.get_glyphs(), line)?
+        )?;
+    }
+    Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn oid(id: u8) -> NonZeroOid {
+        format!("{:040x}", id).parse().unwrap()
+    }
+
+    fn oids(ids: &[u8]) -> HashSet<NonZeroOid> {
+        ids.iter().map(|&id| oid(id)).collect()
+    }
+
+    #[test]
+    fn test_narrow_candidates_unions_ranges_from_each_good_commit() {
+        // Two known-good boundaries, each with their own range up to `bad`;
+        // the candidate set is everything reachable from either.
+        let ranges = vec![oids(&[1, 2, 3]), oids(&[3, 4])];
+        let candidates = narrow_candidates(&ranges, &HashSet::new(), &HashSet::new());
+        assert_eq!(candidates, oids(&[1, 2, 3, 4]));
+    }
+
+    #[test]
+    fn test_narrow_candidates_excludes_obsolete_commits() {
+        let ranges = vec![oids(&[1, 2, 3])];
+        let candidates = narrow_candidates(&ranges, &oids(&[2]), &HashSet::new());
+        assert_eq!(candidates, oids(&[1, 3]));
+    }
+
+    #[test]
+    fn test_narrow_candidates_excludes_skipped_commits() {
+        let ranges = vec![oids(&[1, 2, 3])];
+        let candidates = narrow_candidates(&ranges, &HashSet::new(), &oids(&[1]));
+        assert_eq!(candidates, oids(&[2, 3]));
+    }
+
+    #[test]
+    fn test_pick_most_balanced_picks_the_middle_of_a_linear_chain() {
+        // A linear chain good(1) - 2 - 3 - 4 - bad(5): commit 2 has exactly
+        // half (2) of the 5 candidates as its ancestors (itself and 1).
+        let candidate_oids = vec![oid(1), oid(2), oid(3), oid(4), oid(5)];
+        let ancestor_counts = HashMap::from([
+            (oid(1), 1),
+            (oid(2), 2),
+            (oid(3), 3),
+            (oid(4), 4),
+            (oid(5), 5),
+        ]);
+        assert_eq!(
+            pick_most_balanced(&candidate_oids, &ancestor_counts),
+            Some(oid(2))
+        );
+    }
+
+    #[test]
+    fn test_pick_most_balanced_breaks_ties_towards_first_candidate() {
+        let candidate_oids = vec![oid(1), oid(2)];
+        let ancestor_counts = HashMap::from([(oid(1), 2), (oid(2), 2)]);
+        // Both are equally far (by 1) from half == 1, so the first examined
+        // candidate wins.
+        assert_eq!(
+            pick_most_balanced(&candidate_oids, &ancestor_counts),
+            Some(oid(1))
+        );
+    }
+
+    #[test]
+    fn test_pick_most_balanced_with_no_candidates() {
+        assert_eq!(pick_most_balanced(&[], &HashMap::new()), None);
+    }
+
+    #[test]
+    fn test_pick_most_balanced_after_skip_narrows_remaining_candidates() {
+        // After skipping commit 3 from the linear chain in the test above,
+        // only 1, 2, 4, 5 remain; 2 now has the most balanced ancestor count.
+        let candidate_oids = vec![oid(1), oid(2), oid(4), oid(5)];
+        let ancestor_counts = HashMap::from([
+            (oid(1), 1),
+            (oid(2), 2),
+            (oid(4), 3),
+            (oid(5), 4),
+        ]);
+        assert_eq!(
+            pick_most_balanced(&candidate_oids, &ancestor_counts),
+            Some(oid(2))
+        );
+    }
+}
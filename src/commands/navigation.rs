@@ -13,9 +13,10 @@ use crate::core::eventlog::{EventLogDb, EventReplayer};
 use crate::core::formatting::printable_styled_string;
 use crate::core::metadata::{
     BranchesProvider, CommitMessageProvider, CommitNumberProvider, CommitOidProvider,
-    DifferentialRevisionProvider, ObsolescenceExplanationProvider, RelativeTimeProvider,
+    DescribeProvider, DifferentialRevisionProvider, ObsolescenceExplanationProvider,
+    RelativeTimeProvider,
 };
-use crate::git::{sort_commit_set, CommitSet, Dag, GitRunInfo, NonZeroOid, Repo};
+use crate::git::{sort_commit_set, Commit, CommitSet, Dag, GitRunInfo, NonZeroOid, Repo};
 use crate::tui::Effects;
 
 /// Go back a certain number of commits.
@@ -24,25 +25,64 @@ pub fn prev(
     effects: &Effects,
     git_run_info: &GitRunInfo,
     num_commits: Option<isize>,
+    towards: Option<Towards>,
+    accept: Option<NonZeroOid>,
+    interactive: bool,
+    porcelain: bool,
 ) -> eyre::Result<isize> {
-    let exit_code = match num_commits {
-        None => git_run_info.run(effects, None, &["checkout", "HEAD^"])?,
-        Some(num_commits) => git_run_info.run(
-            effects,
-            None,
-            &["checkout", &format!("HEAD~{}", num_commits)],
-        )?,
+    let repo = Repo::from_current_dir()?;
+    let references_snapshot = repo.get_references_snapshot()?;
+    let conn = repo.get_db_conn()?;
+    let event_log_db = EventLogDb::new(&conn)?;
+    let event_replayer = EventReplayer::from_event_log_db(effects, &repo, &event_log_db)?;
+    let event_cursor = event_replayer.make_default_cursor();
+    let dag = Dag::open_and_sync(
+        effects,
+        &repo,
+        &event_replayer,
+        event_cursor,
+        &references_snapshot,
+    )?;
+
+    let head_oid = match references_snapshot.head_oid {
+        Some(head_oid) => head_oid,
+        None => {
+            eyre::bail!("No HEAD present; cannot calculate previous commit");
+        }
+    };
+
+    let num_commits = num_commits.unwrap_or(1);
+    let current_oid = retreat(
+        effects,
+        &repo,
+        &dag,
+        head_oid,
+        num_commits,
+        towards,
+        accept,
+        interactive,
+        porcelain,
+    )?;
+    let current_oid = match current_oid {
+        None => return Ok(1),
+        Some(current_oid) => current_oid,
     };
-    if exit_code != 0 {
-        return Ok(exit_code);
+
+    let result = git_run_info.run(effects, None, &["checkout", &current_oid.to_string()])?;
+    if result != 0 {
+        return Ok(result);
+    }
+
+    if !porcelain {
+        smartlog(effects, &Default::default())?;
     }
-    smartlog(effects, &Default::default())?;
     Ok(0)
 }
 
-/// Some commits have multiple children, which makes `next` ambiguous. These
-/// values disambiguate which child commit to go to, according to the committed
-/// date.
+/// Some commits have multiple children (or, traversing backwards, multiple
+/// parents, as with a merge commit), which makes `next`/`prev` ambiguous.
+/// These values disambiguate which child or parent commit to go to, according
+/// to the committed date.
 #[derive(Clone, Copy, Debug)]
 pub enum Towards {
     /// When encountering multiple children, select the newest one.
@@ -52,6 +92,52 @@ pub enum Towards {
     Oldest,
 }
 
+/// What to do next given a list of candidate commits (children when
+/// advancing, parents when retreating) and an optional `--oldest`/`--newest`
+/// preference.
+#[derive(Debug, Eq, PartialEq)]
+enum StepResolution {
+    /// There's nowhere to go.
+    NoCandidates,
+
+    /// There's exactly one way to go, or `towards` broke the tie.
+    Resolved(NonZeroOid),
+
+    /// Multiple candidates remain and `towards` didn't disambiguate; the
+    /// caller must resolve this with `--accept`, `--porcelain`, or an
+    /// interactive prompt.
+    Ambiguous,
+}
+
+/// Pure decision logic shared by [`advance`] and [`retreat`]: given the
+/// (deterministically-ordered, oldest-to-newest) candidate commits for this
+/// step, decide whether the step is unambiguous. Extracted so that the
+/// decision can be unit-tested against plain oid lists, without needing to
+/// construct real `Commit`s or a `Dag`.
+fn resolve_step(towards: Option<Towards>, candidate_oids: &[NonZeroOid]) -> StepResolution {
+    match (towards, candidate_oids) {
+        (_, []) => StepResolution::NoCandidates,
+        (_, [only]) => StepResolution::Resolved(*only),
+        (Some(Towards::Newest), [.., newest]) => StepResolution::Resolved(*newest),
+        (Some(Towards::Oldest), [oldest, ..]) => StepResolution::Resolved(*oldest),
+        (None, [_, _, ..]) => StepResolution::Ambiguous,
+    }
+}
+
+/// Keep only the elements of `items` whose corresponding entry in `flags` is
+/// `true`. Used by [`advance`] to narrow candidate children down to just
+/// those lying on the path towards a target; extracted (rather than inlined
+/// as a `zip`/`filter`) so the filtering itself can be unit-tested without
+/// needing to construct real `Commit`s.
+fn filter_by_flags<T>(items: Vec<T>, flags: Vec<bool>) -> Vec<T> {
+    items
+        .into_iter()
+        .zip(flags)
+        .filter(|(_item, flag)| *flag)
+        .map(|(item, _flag)| item)
+        .collect()
+}
+
 #[instrument]
 fn advance(
     effects: &Effects,
@@ -60,7 +146,10 @@ fn advance(
     current_oid: NonZeroOid,
     num_commits: isize,
     towards: Option<Towards>,
+    target: Option<NonZeroOid>,
+    accept: Option<NonZeroOid>,
     interactive: bool,
+    porcelain: bool,
 ) -> eyre::Result<Option<NonZeroOid>> {
     let glyphs = effects.get_glyphs();
     let mut current_oid = current_oid;
@@ -71,53 +160,196 @@ fn advance(
             .difference(&dag.obsolete_commits);
         let children = sort_commit_set(repo, dag, &children)?;
 
-        current_oid = match (towards, children.as_slice()) {
-            (_, []) => {
+        // If we were given a target to advance towards, keep only the
+        // children that actually lie on the path to that target. This lets
+        // us resolve would-be-ambiguous forks automatically. If none of them
+        // does, no candidates remain and advancing stops here.
+        let children = match target {
+            Some(target) if children.len() > 1 => {
+                let children_towards_target = children
+                    .iter()
+                    .map(|child| -> eyre::Result<bool> {
+                        Ok(dag
+                            .query()
+                            .is_ancestor(CommitSet::from(child.get_oid()), CommitSet::from(target))?)
+                    })
+                    .collect::<eyre::Result<Vec<bool>>>()?;
+                filter_by_flags(children, children_towards_target)
+            }
+            Some(_) | None => children,
+        };
+
+        let child_oids: Vec<NonZeroOid> = children.iter().map(|child| child.get_oid()).collect();
+        current_oid = match resolve_step(towards, &child_oids) {
+            StepResolution::NoCandidates => {
                 // It would also make sense to issue an error here, rather than
                 // silently stop going forward commits.
                 break;
             }
-            (_, [only_child]) => only_child.get_oid(),
-            (Some(Towards::Newest), [.., newest_child]) => newest_child.get_oid(),
-            (Some(Towards::Oldest), [oldest_child, ..]) => oldest_child.get_oid(),
-            (None, [_, _, ..]) => {
-                writeln!(
-                    effects.get_output_stream(),
-                    "Found multiple possible next commits to go to after traversing {} children:",
-                    i
-                )?;
-
-                for (j, child) in (0..).zip(children.iter()) {
-                    let prefix = if interactive {
-                        format!(" [{}] ", j + 1)
-                    } else {
-                        "".into()
-                    };
-                    let descriptor = if j == 0 {
-                        " (oldest)"
-                    } else if j + 1 == children.len() {
-                        " (newest)"
-                    } else {
-                        ""
-                    };
-
+            StepResolution::Resolved(oid) => oid,
+            StepResolution::Ambiguous => {
+                if let Some(accept_oid) = accept {
+                    match children.iter().find(|child| child.get_oid() == accept_oid) {
+                        Some(_) => accept_oid,
+                        None => eyre::bail!(
+                            "The commit passed to --accept ({}) is not one of the candidate next commits",
+                            accept_oid
+                        ),
+                    }
+                } else if porcelain {
+                    print_porcelain_candidates(effects, &children)?;
+                    return Ok(None);
+                } else {
                     writeln!(
                         effects.get_output_stream(),
-                        "  {} {}{}{}",
-                        glyphs.bullet_point,
-                        prefix,
-                        printable_styled_string(glyphs, child.friendly_describe()?)?,
-                        descriptor
+                        "Found multiple possible next commits to go to after traversing {} children:",
+                        i
                     )?;
+
+                    for (j, child) in (0..).zip(children.iter()) {
+                        let prefix = if interactive {
+                            format!(" [{}] ", j + 1)
+                        } else {
+                            "".into()
+                        };
+                        let descriptor = if j == 0 {
+                            " (oldest)"
+                        } else if j + 1 == children.len() {
+                            " (newest)"
+                        } else {
+                            ""
+                        };
+
+                        writeln!(
+                            effects.get_output_stream(),
+                            "  {} {}{}{}",
+                            glyphs.bullet_point,
+                            prefix,
+                            printable_styled_string(glyphs, child.friendly_describe()?)?,
+                            descriptor
+                        )?;
+                    }
+                    if interactive {
+                        match prompt_for_range(effects, 1, children.len())? {
+                            Some(selected) => children[selected - 1].get_oid(),
+                            None => return Ok(None),
+                        }
+                    } else {
+                        writeln!(effects.get_output_stream(), "(Pass --oldest (-o) or --newest (-n) to select between ambiguous next commits)")?;
+                        return Ok(None);
+                    }
                 }
-                if interactive {
-                    match prompt_for_range(effects, 1, children.len())? {
-                        Some(selected) => children[selected - 1].get_oid(),
-                        None => return Ok(None),
+            }
+        };
+    }
+    Ok(Some(current_oid))
+}
+
+/// Emit the candidate commits in a stable, machine-readable form: one
+/// candidate per line, as `<oid>\t<oldest|newest|>\t<description>`. Used by
+/// `--porcelain` callers (editor/TUI integrations) that want to enumerate an
+/// ambiguous navigation choice themselves rather than going through
+/// [`prompt_for_range`].
+fn print_porcelain_candidates(effects: &Effects, candidates: &[Commit]) -> eyre::Result<()> {
+    for (j, candidate) in (0..).zip(candidates.iter()) {
+        let descriptor = if j == 0 {
+            "oldest"
+        } else if j + 1 == candidates.len() {
+            "newest"
+        } else {
+            ""
+        };
+        writeln!(
+            effects.get_output_stream(),
+            "{}\t{}\t{}",
+            candidate.get_oid(),
+            descriptor,
+            printable_styled_string(effects.get_glyphs(), candidate.friendly_describe()?)?
+        )?;
+    }
+    Ok(())
+}
+
+#[instrument]
+fn retreat(
+    effects: &Effects,
+    repo: &Repo,
+    dag: &Dag,
+    current_oid: NonZeroOid,
+    num_commits: isize,
+    towards: Option<Towards>,
+    accept: Option<NonZeroOid>,
+    interactive: bool,
+    porcelain: bool,
+) -> eyre::Result<Option<NonZeroOid>> {
+    let glyphs = effects.get_glyphs();
+    let mut current_oid = current_oid;
+    for i in 0..num_commits {
+        let parents = dag
+            .query()
+            .parents(CommitSet::from(current_oid))?
+            .difference(&dag.obsolete_commits);
+        let parents = sort_commit_set(repo, dag, &parents)?;
+
+        let parent_oids: Vec<NonZeroOid> = parents.iter().map(|parent| parent.get_oid()).collect();
+        current_oid = match resolve_step(towards, &parent_oids) {
+            StepResolution::NoCandidates => {
+                // It would also make sense to issue an error here, rather than
+                // silently stop going backward commits.
+                break;
+            }
+            StepResolution::Resolved(oid) => oid,
+            StepResolution::Ambiguous => {
+                if let Some(accept_oid) = accept {
+                    match parents.iter().find(|parent| parent.get_oid() == accept_oid) {
+                        Some(_) => accept_oid,
+                        None => eyre::bail!(
+                            "The commit passed to --accept ({}) is not one of the candidate previous commits",
+                            accept_oid
+                        ),
                     }
-                } else {
-                    writeln!(effects.get_output_stream(), "(Pass --oldest (-o) or --newest (-n) to select between ambiguous next commits)")?;
+                } else if porcelain {
+                    print_porcelain_candidates(effects, &parents)?;
                     return Ok(None);
+                } else {
+                    writeln!(
+                        effects.get_output_stream(),
+                        "Found multiple possible previous commits to go to after traversing {} parents:",
+                        i
+                    )?;
+
+                    for (j, parent) in (0..).zip(parents.iter()) {
+                        let prefix = if interactive {
+                            format!(" [{}] ", j + 1)
+                        } else {
+                            "".into()
+                        };
+                        let descriptor = if j == 0 {
+                            " (oldest)"
+                        } else if j + 1 == parents.len() {
+                            " (newest)"
+                        } else {
+                            ""
+                        };
+
+                        writeln!(
+                            effects.get_output_stream(),
+                            "  {} {}{}{}",
+                            glyphs.bullet_point,
+                            prefix,
+                            printable_styled_string(glyphs, parent.friendly_describe()?)?,
+                            descriptor
+                        )?;
+                    }
+                    if interactive {
+                        match prompt_for_range(effects, 1, parents.len())? {
+                            Some(selected) => parents[selected - 1].get_oid(),
+                            None => return Ok(None),
+                        }
+                    } else {
+                        writeln!(effects.get_output_stream(), "(Pass --oldest (-o) or --newest (-n) to select between ambiguous previous commits)")?;
+                        return Ok(None);
+                    }
                 }
             }
         };
@@ -132,7 +364,10 @@ pub fn next(
     git_run_info: &GitRunInfo,
     num_commits: Option<isize>,
     towards: Option<Towards>,
+    target: Option<String>,
+    accept: Option<NonZeroOid>,
     interactive: bool,
+    porcelain: bool,
 ) -> eyre::Result<isize> {
     let repo = Repo::from_current_dir()?;
     let references_snapshot = repo.get_references_snapshot()?;
@@ -155,6 +390,13 @@ pub fn next(
         }
     };
 
+    let target = target
+        .map(|target| -> eyre::Result<NonZeroOid> {
+            let commit = repo.revparse_single_commit(&target)?;
+            Ok(commit.get_oid())
+        })
+        .transpose()?;
+
     let num_commits = num_commits.unwrap_or(1);
     let current_oid = advance(
         effects,
@@ -163,7 +405,10 @@ pub fn next(
         head_oid,
         num_commits,
         towards,
+        target,
+        accept,
         interactive,
+        porcelain,
     )?;
     let current_oid = match current_oid {
         None => return Ok(1),
@@ -175,13 +420,20 @@ pub fn next(
         return Ok(result);
     }
 
-    smartlog(effects, &Default::default())?;
+    if !porcelain {
+        smartlog(effects, &Default::default())?;
+    }
     Ok(0)
 }
 
 /// Pick a specific commit to checkout.
 #[instrument]
-pub fn pick(effects: &Effects, git_run_info: &GitRunInfo) -> eyre::Result<isize> {
+pub fn pick(
+    effects: &Effects,
+    git_run_info: &GitRunInfo,
+    accept: Option<NonZeroOid>,
+    porcelain: bool,
+) -> eyre::Result<isize> {
     let repo = Repo::from_current_dir()?;
     let references_snapshot = repo.get_references_snapshot()?;
     let conn = repo.get_db_conn()?;
@@ -201,6 +453,28 @@ pub fn pick(effects: &Effects, git_run_info: &GitRunInfo) -> eyre::Result<isize>
     let root_oids = render::split_commit_graph_by_roots(effects, &repo, &dag, &graph);
     let numbered_nodes = number_nodes(&graph, &root_oids);
 
+    if let Some(accept_oid) = accept {
+        if !numbered_nodes.iter().any(|(oid, _)| *oid == accept_oid) {
+            eyre::bail!(
+                "The commit passed to --accept ({}) is not one of the candidate commits",
+                accept_oid
+            );
+        }
+        let result = git_run_info.run(effects, None, &["checkout", &accept_oid.to_string()])?;
+        return Ok(result);
+    }
+
+    if porcelain {
+        for (oid, number) in numbered_nodes.iter() {
+            let description = match repo.find_commit(*oid)? {
+                Some(commit) => printable_styled_string(effects.get_glyphs(), commit.friendly_describe()?)?,
+                None => String::new(),
+            };
+            writeln!(effects.get_output_stream(), "{}\t{}\t{}", oid, number, description)?;
+        }
+        return Ok(0);
+    }
+
     let lines = render_graph(
         effects,
         &repo,
@@ -212,6 +486,7 @@ pub fn pick(effects: &Effects, git_run_info: &GitRunInfo) -> eyre::Result<isize>
             &mut RelativeTimeProvider::new(&repo, SystemTime::now())?,
             &mut ObsolescenceExplanationProvider::new(&event_replayer, event_cursor)?,
             &mut BranchesProvider::new(&repo, &references_snapshot)?,
+            &mut DescribeProvider::new(&repo, &references_snapshot)?,
             &mut DifferentialRevisionProvider::new(&repo)?,
             &mut CommitMessageProvider::new()?,
             &mut CommitNumberProvider::new(&numbered_nodes)?,
@@ -265,3 +540,65 @@ fn prompt_for_range(effects: &Effects, min: usize, max: usize) -> eyre::Result<O
         Ok(Some(selected))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn oid(id: u8) -> NonZeroOid {
+        format!("{:040x}", id).parse().unwrap()
+    }
+
+    #[test]
+    fn test_resolve_step_with_no_candidates() {
+        assert_eq!(resolve_step(None, &[]), StepResolution::NoCandidates);
+    }
+
+    #[test]
+    fn test_resolve_step_with_a_single_candidate_ignores_towards() {
+        assert_eq!(
+            resolve_step(Some(Towards::Oldest), &[oid(1)]),
+            StepResolution::Resolved(oid(1))
+        );
+    }
+
+    #[test]
+    fn test_resolve_step_with_multiple_candidates_and_no_towards_is_ambiguous() {
+        assert_eq!(
+            resolve_step(None, &[oid(1), oid(2)]),
+            StepResolution::Ambiguous
+        );
+    }
+
+    #[test]
+    fn test_resolve_step_picks_newest() {
+        assert_eq!(
+            resolve_step(Some(Towards::Newest), &[oid(1), oid(2), oid(3)]),
+            StepResolution::Resolved(oid(3))
+        );
+    }
+
+    #[test]
+    fn test_resolve_step_picks_oldest() {
+        assert_eq!(
+            resolve_step(Some(Towards::Oldest), &[oid(1), oid(2), oid(3)]),
+            StepResolution::Resolved(oid(1))
+        );
+    }
+
+    #[test]
+    fn test_filter_by_flags_keeps_only_flagged_elements() {
+        assert_eq!(
+            filter_by_flags(vec![oid(1), oid(2), oid(3)], vec![false, true, true]),
+            vec![oid(2), oid(3)]
+        );
+    }
+
+    #[test]
+    fn test_filter_by_flags_with_nothing_flagged() {
+        assert_eq!(
+            filter_by_flags(vec![oid(1), oid(2)], vec![false, false]),
+            Vec::<NonZeroOid>::new()
+        );
+    }
+}
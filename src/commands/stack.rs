@@ -0,0 +1,308 @@
+//! Validates the relative positions of a small set of tracking branches
+//! (`main`, a staging branch, and the current working tip) and can advance
+//! the staging branch forward by exactly one commit along the path to the
+//! tip.
+
+use std::fmt::Write as _;
+use std::io::stdin;
+
+use eden_dag::DagAlgorithm;
+use tracing::instrument;
+
+use crate::config::{get_main_branch_name, get_staging_branch_name};
+use crate::core::eventlog::{EventLogDb, EventReplayer};
+use crate::git::{sort_commit_set, CommitSet, Dag, GitRunInfo, NonZeroOid, Repo};
+use crate::tui::Effects;
+
+/// The result of checking whether the staging branch is still on the path to
+/// the tip.
+#[derive(Debug, Eq, PartialEq)]
+enum StagingBranchStatus {
+    /// The staging branch is an ancestor of the tip, so it's safe to advance
+    /// it further.
+    UpToDate,
+
+    /// The staging branch is not an ancestor of the tip, so it has diverged
+    /// and needs to be reset before it can be advanced again.
+    Diverged {
+        /// Where the staging branch should be reset to: either `main`, or,
+        /// if the tip is ahead of `main`, the first commit on the path from
+        /// `main` towards the tip.
+        reset_target_oid: NonZeroOid,
+    },
+}
+
+/// Check that `staging_oid` is an ancestor of `tip_oid`, i.e. that the
+/// staging branch hasn't diverged from the stack it's meant to be tracking.
+#[instrument]
+fn check_staging_branch_status(
+    repo: &Repo,
+    dag: &Dag,
+    main_oid: NonZeroOid,
+    staging_oid: NonZeroOid,
+    tip_oid: NonZeroOid,
+) -> eyre::Result<StagingBranchStatus> {
+    let staging_is_ancestor_of_tip = dag
+        .query()
+        .is_ancestor(CommitSet::from(staging_oid), CommitSet::from(tip_oid))?;
+    let tip_ahead_of_main = dag
+        .query()
+        .is_ancestor(CommitSet::from(main_oid), CommitSet::from(tip_oid))?;
+    let next_commit_from_main_towards_tip = if !staging_is_ancestor_of_tip && tip_ahead_of_main {
+        next_commit_towards(repo, dag, main_oid, tip_oid)?
+    } else {
+        None
+    };
+    Ok(decide_staging_branch_status(
+        staging_is_ancestor_of_tip,
+        tip_ahead_of_main,
+        next_commit_from_main_towards_tip,
+        main_oid,
+    ))
+}
+
+/// Pure decision logic underlying [`check_staging_branch_status`]: given
+/// whether the staging branch is already an ancestor of the tip and, if not,
+/// whether the tip is itself ahead of `main`, decide where (if anywhere) the
+/// staging branch needs to be reset to. Extracted so that the divergence
+/// decision can be unit-tested directly, without needing a real `Dag`.
+fn decide_staging_branch_status(
+    staging_is_ancestor_of_tip: bool,
+    tip_ahead_of_main: bool,
+    next_commit_from_main_towards_tip: Option<NonZeroOid>,
+    main_oid: NonZeroOid,
+) -> StagingBranchStatus {
+    if staging_is_ancestor_of_tip {
+        return StagingBranchStatus::UpToDate;
+    }
+    let reset_target_oid = if tip_ahead_of_main {
+        next_commit_from_main_towards_tip.unwrap_or(main_oid)
+    } else {
+        main_oid
+    };
+    StagingBranchStatus::Diverged { reset_target_oid }
+}
+
+/// Find the child of `from_oid` that lies on the ancestry path towards
+/// `towards_oid`, i.e. the next commit to move to if walking forward one
+/// commit at a time from `from_oid` to `towards_oid`.
+#[instrument]
+fn next_commit_towards(
+    repo: &Repo,
+    dag: &Dag,
+    from_oid: NonZeroOid,
+    towards_oid: NonZeroOid,
+) -> eyre::Result<Option<NonZeroOid>> {
+    if from_oid == towards_oid {
+        return Ok(None);
+    }
+
+    let children = dag
+        .query()
+        .children(CommitSet::from(from_oid))?
+        .difference(&dag.obsolete_commits);
+    let children = sort_commit_set(repo, dag, &children)?;
+
+    let mut children_on_path = Vec::new();
+    for child in children.iter() {
+        let child_oid = child.get_oid();
+        let is_on_path = dag
+            .query()
+            .is_ancestor(CommitSet::from(child_oid), CommitSet::from(towards_oid))?;
+        children_on_path.push((child_oid, is_on_path));
+    }
+    Ok(pick_child_towards(&children_on_path))
+}
+
+/// Pure selection logic underlying [`next_commit_towards`]: given the
+/// (deterministically-ordered) children of a commit, each paired with
+/// whether it lies on the ancestry path towards the target, pick the first
+/// one that does. Extracted so that the selection can be unit-tested against
+/// hand-built candidate lists representing small synthetic DAGs, without
+/// needing a real `Dag`.
+fn pick_child_towards(children_on_path: &[(NonZeroOid, bool)]) -> Option<NonZeroOid> {
+    children_on_path
+        .iter()
+        .find(|(_, is_on_path)| *is_on_path)
+        .map(|(oid, _)| *oid)
+}
+
+/// Ask the user to confirm an action that isn't safely reversible, such as
+/// force-moving a branch. Defaults to "no" on an empty or unrecognized
+/// response, matching the fail-closed behavior of [`prompt_for_range`] in
+/// `navigation.rs`.
+fn prompt_to_confirm(effects: &Effects, prompt: &str) -> eyre::Result<bool> {
+    write!(effects.get_output_stream(), "{} [y/N] ", prompt)?;
+    let mut in_ = String::new();
+    stdin().read_line(&mut in_)?;
+    Ok(matches!(in_.trim(), "y" | "Y" | "yes"))
+}
+
+/// Validate the staging branch's position and, if it's still on the path to
+/// the tip, advance it forward by one commit. If it's diverged, offer to
+/// reset it onto `main` (or, if the tip is ahead of `main`, onto the first
+/// commit on the path from `main` towards the tip); this force-moves the branch, so it's gated
+/// behind confirmation unless `yes` is set.
+#[instrument]
+pub fn promote(effects: &Effects, git_run_info: &GitRunInfo, yes: bool) -> eyre::Result<isize> {
+    let repo = Repo::from_current_dir()?;
+    let references_snapshot = repo.get_references_snapshot()?;
+    let conn = repo.get_db_conn()?;
+    let event_log_db = EventLogDb::new(&conn)?;
+    let event_replayer = EventReplayer::from_event_log_db(effects, &repo, &event_log_db)?;
+    let event_cursor = event_replayer.make_default_cursor();
+    let dag = Dag::open_and_sync(
+        effects,
+        &repo,
+        &event_replayer,
+        event_cursor,
+        &references_snapshot,
+    )?;
+
+    let staging_branch_name = match get_staging_branch_name(repo.get_repo())? {
+        Some(staging_branch_name) => staging_branch_name,
+        None => {
+            eyre::bail!(
+                "No staging branch configured; set `branchless.stagingBranch` to enable `git promote`"
+            );
+        }
+    };
+    let main_branch_name = get_main_branch_name(repo.get_repo())?;
+
+    let main_oid = repo.revparse_single_commit(&main_branch_name)?.get_oid();
+    let staging_oid = repo.revparse_single_commit(&staging_branch_name)?.get_oid();
+    let tip_oid = match references_snapshot.head_oid {
+        Some(tip_oid) => tip_oid,
+        None => eyre::bail!("No HEAD present; cannot determine the current tip"),
+    };
+
+    match check_staging_branch_status(&repo, &dag, main_oid, staging_oid, tip_oid)? {
+        StagingBranchStatus::Diverged { reset_target_oid } => {
+            if !yes
+                && !prompt_to_confirm(
+                    effects,
+                    &format!(
+                        "Branch {} has diverged from the current stack; reset it to {}?",
+                        staging_branch_name, reset_target_oid
+                    ),
+                )?
+            {
+                writeln!(effects.get_output_stream(), "Aborted.")?;
+                return Ok(1);
+            }
+            writeln!(
+                effects.get_output_stream(),
+                "Resetting {} to {}.",
+                staging_branch_name,
+                reset_target_oid
+            )?;
+            let result = git_run_info.run(
+                effects,
+                None,
+                &[
+                    "update-ref",
+                    &format!("refs/heads/{}", staging_branch_name),
+                    &reset_target_oid.to_string(),
+                ],
+            )?;
+            Ok(result)
+        }
+        StagingBranchStatus::UpToDate => {
+            let next_oid = next_commit_towards(&repo, &dag, staging_oid, tip_oid)?;
+            let next_oid = match next_oid {
+                Some(next_oid) => next_oid,
+                None => {
+                    writeln!(
+                        effects.get_output_stream(),
+                        "Branch {} is already at the tip; nothing to promote.",
+                        staging_branch_name
+                    )?;
+                    return Ok(0);
+                }
+            };
+            writeln!(
+                effects.get_output_stream(),
+                "Advancing {} to {}.",
+                staging_branch_name,
+                next_oid
+            )?;
+            let result = git_run_info.run(
+                effects,
+                None,
+                &[
+                    "update-ref",
+                    &format!("refs/heads/{}", staging_branch_name),
+                    &next_oid.to_string(),
+                ],
+            )?;
+            Ok(result)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn oid(id: u8) -> NonZeroOid {
+        format!("{:040x}", id).parse().unwrap()
+    }
+
+    #[test]
+    fn test_decide_staging_branch_status_up_to_date() {
+        let status = decide_staging_branch_status(true, true, Some(oid(2)), oid(1));
+        assert_eq!(status, StagingBranchStatus::UpToDate);
+    }
+
+    #[test]
+    fn test_decide_staging_branch_status_diverged_resets_to_main_when_tip_behind() {
+        // The tip isn't ahead of main, so there's no meaningful point between
+        // them to reset to; fall back to main itself.
+        let status = decide_staging_branch_status(false, false, None, oid(1));
+        assert_eq!(
+            status,
+            StagingBranchStatus::Diverged {
+                reset_target_oid: oid(1)
+            }
+        );
+    }
+
+    #[test]
+    fn test_decide_staging_branch_status_diverged_resets_to_path_when_tip_ahead() {
+        let status = decide_staging_branch_status(false, true, Some(oid(2)), oid(1));
+        assert_eq!(
+            status,
+            StagingBranchStatus::Diverged {
+                reset_target_oid: oid(2)
+            }
+        );
+    }
+
+    #[test]
+    fn test_decide_staging_branch_status_diverged_falls_back_to_main_if_no_path_found() {
+        let status = decide_staging_branch_status(false, true, None, oid(1));
+        assert_eq!(
+            status,
+            StagingBranchStatus::Diverged {
+                reset_target_oid: oid(1)
+            }
+        );
+    }
+
+    #[test]
+    fn test_pick_child_towards_picks_the_first_match() {
+        let children = vec![(oid(1), false), (oid(2), true), (oid(3), true)];
+        assert_eq!(pick_child_towards(&children), Some(oid(2)));
+    }
+
+    #[test]
+    fn test_pick_child_towards_with_no_match() {
+        let children = vec![(oid(1), false), (oid(2), false)];
+        assert_eq!(pick_child_towards(&children), None);
+    }
+
+    #[test]
+    fn test_pick_child_towards_with_no_children() {
+        assert_eq!(pick_child_towards(&[]), None);
+    }
+}
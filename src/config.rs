@@ -13,6 +13,30 @@ pub fn get_main_branch_name(repo: &git2::Repository) -> anyhow::Result<String> {
         .or_else(|_| Ok(String::from("master")))
 }
 
+/// Get the name of the staging branch for the repository, if one has been
+/// configured. The staging branch is expected to always point at an
+/// ancestor of the current working tip, and can be advanced one commit at a
+/// time towards it; see the `promote` command.
+pub fn get_staging_branch_name(repo: &git2::Repository) -> anyhow::Result<Option<String>> {
+    let config = repo.config()?;
+    match config.get_string("branchless.stagingBranch") {
+        Ok(staging_branch_name) => Ok(Some(staging_branch_name)),
+        Err(_) => Ok(None),
+    }
+}
+
+/// The maximum number of commits to walk back through history when looking
+/// for a named ancestor to describe a commit by (see `DescribeProvider`). If
+/// no named ancestor is found within this many commits, the search gives up
+/// and falls back to the abbreviated commit hash.
+pub fn get_describe_max_depth(repo: &git2::Repository) -> anyhow::Result<usize> {
+    let config = repo.config()?;
+    let max_depth = config
+        .get_i64("branchless.describe.maxDepth")
+        .unwrap_or(1000);
+    Ok(max_depth.max(0) as usize)
+}
+
 /// If `true`, when restacking a commit, do not update its timestamp to the
 /// current time.
 ///
@@ -0,0 +1,266 @@
+//! Metadata providers which annotate each commit rendered in the smartlog
+//! with some piece of information about it, such as its message or the
+//! nearest named ref.
+
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use crate::config::get_describe_max_depth;
+use crate::git::{Commit, NonZeroOid, ReferencesSnapshot, Repo};
+
+/// A single piece of metadata to render alongside a commit in the smartlog,
+/// such as its message, its age, or (as implemented by [`DescribeProvider`])
+/// its distance from the nearest named ref.
+pub trait CommitMetadataProvider {
+    /// Compute the metadata string for `commit`, or `None` if this provider
+    /// has nothing to say about it.
+    fn provide_metadata(&mut self, commit: &Commit) -> eyre::Result<Option<String>>;
+}
+
+/// Annotates each commit with a `git describe`-like label, e.g.
+/// `master-3-gabc1234`: the name of the nearest reachable branch or tag, plus
+/// the number of commits between it and the commit being described. Falls
+/// back to the abbreviated commit hash if no named ancestor can be found
+/// within `max_depth` commits.
+pub struct DescribeProvider<'repo> {
+    repo: &'repo Repo,
+    names: HashMap<NonZeroOid, String>,
+    max_depth: usize,
+}
+
+/// A commit queued for traversal during the describe walk, ordered by
+/// committer date so that the best-first search visits the newest commits
+/// first.
+struct QueueEntry {
+    committer_time: i64,
+    oid: NonZeroOid,
+}
+
+impl PartialEq for QueueEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.committer_time == other.committer_time
+    }
+}
+
+impl Eq for QueueEntry {}
+
+impl PartialOrd for QueueEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueueEntry {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        // `BinaryHeap` is a max-heap, and we want to visit the newest commits
+        // first, so compare directly on committer time.
+        self.committer_time.cmp(&other.committer_time)
+    }
+}
+
+impl<'repo> DescribeProvider<'repo> {
+    /// Constructor. Collects the names of all branches and tags in
+    /// `references_snapshot` up front, so that each call to
+    /// [`Self::describe_commit`] only has to perform the graph walk.
+    pub fn new(repo: &'repo Repo, references_snapshot: &ReferencesSnapshot) -> eyre::Result<Self> {
+        let mut names = HashMap::new();
+        for (oid, branch_names) in references_snapshot.branch_oid_to_names.iter() {
+            if let Some(branch_name) = branch_names.iter().next() {
+                names.insert(*oid, branch_name.clone());
+            }
+        }
+        // Tags are also valid describe anchors; prefer a branch name where
+        // both are present on the same commit, since branches are more
+        // likely to still be meaningful to the user.
+        for (oid, tag_names) in references_snapshot.tag_oid_to_names.iter() {
+            if let Some(tag_name) = tag_names.iter().next() {
+                names.entry(*oid).or_insert_with(|| tag_name.clone());
+            }
+        }
+        let max_depth = get_describe_max_depth(repo.get_repo())?;
+        Ok(Self {
+            repo,
+            names,
+            max_depth,
+        })
+    }
+
+    /// Compute the describe-style label for `commit`.
+    fn describe_commit(&mut self, commit: &Commit) -> eyre::Result<String> {
+        let oid = commit.get_oid();
+        let committer_time = commit.get_time().seconds();
+
+        // Walk parents one at a time via `self.repo`, handing each one's
+        // committer time to the pure search so that it never has to touch
+        // the repo itself; this lets `find_describe_anchor` be unit-tested
+        // against a hand-built parent map standing in for a real `Dag`.
+        let mut get_parents = |oid: NonZeroOid| -> eyre::Result<Vec<(NonZeroOid, i64)>> {
+            let current_commit = match self.repo.find_commit(oid)? {
+                Some(current_commit) => current_commit,
+                None => return Ok(Vec::new()),
+            };
+            let mut parents = Vec::new();
+            for parent_oid in current_commit.get_parent_oids() {
+                let parent_commit = match self.repo.find_commit(parent_oid)? {
+                    Some(parent_commit) => parent_commit,
+                    None => continue,
+                };
+                parents.push((parent_oid, parent_commit.get_time().seconds()));
+            }
+            Ok(parents)
+        };
+
+        match find_describe_anchor(oid, committer_time, &self.names, self.max_depth, |oid| {
+            get_parents(oid)
+        })? {
+            Some((name, depth)) if depth == 0 => Ok(name),
+            Some((name, depth)) => Ok(format!("{}-{}-g{}", name, depth, oid.to_string_short())),
+            // No named ancestor was found within `max_depth` commits; fall
+            // back to the abbreviated OID alone.
+            None => Ok(oid.to_string_short()),
+        }
+    }
+}
+
+/// Best-first search (newest commits first) from `start_oid` for the nearest
+/// ancestor with a name in `names`, walking at most `max_depth` commits.
+/// Returns the name found and its depth below `start_oid`, or `None` if no
+/// named ancestor was found in range.
+///
+/// `get_parents` yields each commit's direct parents along with their
+/// committer times; it's a closure rather than a `Repo`/`Dag` reference so
+/// that this function can be driven by hand-built parent maps representing
+/// small synthetic histories in tests.
+fn find_describe_anchor(
+    start_oid: NonZeroOid,
+    start_committer_time: i64,
+    names: &HashMap<NonZeroOid, String>,
+    max_depth: usize,
+    mut get_parents: impl FnMut(NonZeroOid) -> eyre::Result<Vec<(NonZeroOid, i64)>>,
+) -> eyre::Result<Option<(String, usize)>> {
+    let mut queue = BinaryHeap::new();
+    let mut seen = HashSet::new();
+    queue.push(QueueEntry {
+        committer_time: start_committer_time,
+        oid: start_oid,
+    });
+    seen.insert(start_oid);
+
+    let mut commits_seen = 0;
+    while let Some(QueueEntry { oid, .. }) = queue.pop() {
+        commits_seen += 1;
+
+        if let Some(name) = names.get(&oid) {
+            return Ok(Some((name.clone(), commits_seen - 1)));
+        }
+
+        if commits_seen >= max_depth {
+            break;
+        }
+
+        for (parent_oid, parent_committer_time) in get_parents(oid)? {
+            if !seen.insert(parent_oid) {
+                // Already queued (or visited) via another path, e.g. the
+                // other side of a merge; don't double-count it.
+                continue;
+            }
+            queue.push(QueueEntry {
+                committer_time: parent_committer_time,
+                oid: parent_oid,
+            });
+        }
+    }
+
+    Ok(None)
+}
+
+impl<'repo> CommitMetadataProvider for DescribeProvider<'repo> {
+    fn provide_metadata(&mut self, commit: &Commit) -> eyre::Result<Option<String>> {
+        Ok(Some(self.describe_commit(commit)?))
+    }
+}
+
+/// Marks which commits are still under suspicion in an in-progress `bisect`
+/// search, so that the smartlog re-rendered after each classification can
+/// highlight the remaining candidate region.
+pub struct CandidateHighlightProvider {
+    candidate_oids: HashSet<NonZeroOid>,
+}
+
+impl CandidateHighlightProvider {
+    /// Constructor.
+    pub fn new(candidate_oids: HashSet<NonZeroOid>) -> Self {
+        Self { candidate_oids }
+    }
+}
+
+impl CommitMetadataProvider for CandidateHighlightProvider {
+    fn provide_metadata(&mut self, commit: &Commit) -> eyre::Result<Option<String>> {
+        Ok(if self.candidate_oids.contains(&commit.get_oid()) {
+            Some("candidate".to_string())
+        } else {
+            None
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn oid(id: u8) -> NonZeroOid {
+        format!("{:040x}", id).parse().unwrap()
+    }
+
+    #[test]
+    fn test_find_describe_anchor_finds_the_start_commit_itself() {
+        let names = HashMap::from([(oid(1), "master".to_string())]);
+        let result = find_describe_anchor(oid(1), 0, &names, 1000, |_| Ok(Vec::new())).unwrap();
+        assert_eq!(result, Some(("master".to_string(), 0)));
+    }
+
+    #[test]
+    fn test_find_describe_anchor_counts_depth_along_a_linear_chain() {
+        // 1 (named) <- 2 <- 3 (start): depth 2 below the start commit.
+        let names = HashMap::from([(oid(1), "master".to_string())]);
+        let parents = HashMap::from([
+            (oid(3), vec![(oid(2), 2)]),
+            (oid(2), vec![(oid(1), 1)]),
+            (oid(1), vec![]),
+        ]);
+        let result = find_describe_anchor(oid(3), 3, &names, 1000, |oid| {
+            Ok(parents.get(&oid).cloned().unwrap_or_default())
+        })
+        .unwrap();
+        assert_eq!(result, Some(("master".to_string(), 2)));
+    }
+
+    #[test]
+    fn test_find_describe_anchor_gives_up_past_max_depth() {
+        let names = HashMap::from([(oid(1), "master".to_string())]);
+        let parents = HashMap::from([(oid(2), vec![(oid(1), 1)])]);
+        let result = find_describe_anchor(oid(2), 2, &names, 1, |oid| {
+            Ok(parents.get(&oid).cloned().unwrap_or_default())
+        })
+        .unwrap();
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn test_find_describe_anchor_does_not_revisit_a_commit_reached_via_two_parents() {
+        // A merge commit whose two parents both lead back to the same named
+        // ancestor; it should only be counted once. Commits 4, 2 and 3 are
+        // all walked before the anchor, so the depth is 3.
+        let names = HashMap::from([(oid(1), "master".to_string())]);
+        let parents = HashMap::from([
+            (oid(4), vec![(oid(2), 2), (oid(3), 2)]),
+            (oid(2), vec![(oid(1), 1)]),
+            (oid(3), vec![(oid(1), 1)]),
+            (oid(1), vec![]),
+        ]);
+        let result = find_describe_anchor(oid(4), 3, &names, 1000, |oid| {
+            Ok(parents.get(&oid).cloned().unwrap_or_default())
+        })
+        .unwrap();
+        assert_eq!(result, Some(("master".to_string(), 3)));
+    }
+}